@@ -1,16 +1,28 @@
 use rayon::prelude::*;
-use rusqlite::{params, Connection, Result as SqliteResult};
-use std::fs;
+use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
 use std::path::Path;
-use std::sync::mpsc::channel;
+use std::sync::mpsc::sync_channel;
+use std::sync::OnceLock;
+use std::thread;
 use std::time::SystemTime;
 use walkdir::WalkDir;
 use pyo3::prelude::*;
 use pyo3::types::PyModule;
 use chrono;
+use refinery::embed_migrations;
+use serde::{Deserialize, Serialize};
+
+/// Size of the blocks streamed through the BLAKE3 hasher when content-hashing
+/// a file, chosen to bound memory use on very large files.
+const HASH_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+embed_migrations!("migrations");
 
 #[pyclass]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct FileInfo {
     #[pyo3(get)]
     file_name: String,
@@ -24,6 +36,10 @@ struct FileInfo {
     update_date: Option<String>,
     #[pyo3(get)]
     full_path: String,
+    #[pyo3(get)]
+    file_size: u64,
+    #[pyo3(get)]
+    content_hash: Option<String>,
 }
 
 fn system_time_to_iso8601(time: SystemTime) -> String {
@@ -31,7 +47,23 @@ fn system_time_to_iso8601(time: SystemTime) -> String {
     datetime.to_rfc3339()
 }
 
-fn scan_file(file: &Path) -> Option<FileInfo> {
+/// Streams `file` through a BLAKE3 hasher in `HASH_CHUNK_SIZE` blocks instead
+/// of reading it fully into memory, returning the hex-encoded digest.
+fn hash_file_contents(file: &Path) -> Option<String> {
+    let mut reader = File::open(file).ok()?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; HASH_CHUNK_SIZE];
+    loop {
+        let read = reader.read(&mut buf).ok()?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Some(hasher.finalize().to_hex().to_string())
+}
+
+fn scan_file(file: &Path, hash_contents: bool) -> Option<FileInfo> {
     let metadata = fs::metadata(file).ok()?;
     let file_name = file.file_name()?.to_string_lossy().to_string();
     let file_path = file.parent()?.to_string_lossy().to_string();
@@ -39,6 +71,12 @@ fn scan_file(file: &Path) -> Option<FileInfo> {
     let last_access_date = metadata.accessed().ok().map(system_time_to_iso8601);
     let creation_date = metadata.created().ok().map(system_time_to_iso8601);
     let update_date = metadata.modified().ok().map(system_time_to_iso8601);
+    let file_size = metadata.len();
+    let content_hash = if hash_contents {
+        hash_file_contents(file)
+    } else {
+        None
+    };
 
     Some(FileInfo {
         file_name,
@@ -47,81 +85,647 @@ fn scan_file(file: &Path) -> Option<FileInfo> {
         creation_date,
         update_date,
         full_path,
+        file_size,
+        content_hash,
     })
 }
 
-fn save_batch_to_db(conn: &mut Connection, batch: &[FileInfo]) -> SqliteResult<()> {
+/// A pluggable hook for pulling searchable text out of a file so it can be fed
+/// into `files_fts`. Built-in extractors are dispatched by extension; new ones
+/// (PDF, archives, ...) can be added without touching the scan pipeline.
+trait Extractor {
+    fn matches(&self, ext: &str) -> bool;
+    fn extract(&self, path: &Path) -> Option<String>;
+}
+
+/// Caps how much of a matched file is read into the full-text index, so a
+/// multi-gigabyte log or source file doesn't get slurped whole into memory
+/// on every scan. Files larger than this are simply left out of `files_fts`.
+const MAX_EXTRACT_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Reads up to `MAX_EXTRACT_BYTES` of `path` as text, skipping files that
+/// exceed the cap instead of reading them fully.
+fn read_text_capped(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    if file.metadata().ok()?.len() > MAX_EXTRACT_BYTES {
+        return None;
+    }
+    let mut text = String::new();
+    file.take(MAX_EXTRACT_BYTES).read_to_string(&mut text).ok()?;
+    Some(text)
+}
+
+struct PlainTextExtractor;
+
+impl Extractor for PlainTextExtractor {
+    fn matches(&self, ext: &str) -> bool {
+        matches!(ext, "txt" | "log" | "csv")
+    }
+
+    fn extract(&self, path: &Path) -> Option<String> {
+        read_text_capped(path)
+    }
+}
+
+struct MarkdownExtractor;
+
+impl Extractor for MarkdownExtractor {
+    fn matches(&self, ext: &str) -> bool {
+        matches!(ext, "md" | "markdown")
+    }
+
+    fn extract(&self, path: &Path) -> Option<String> {
+        read_text_capped(path)
+    }
+}
+
+struct SourceExtractor;
+
+impl Extractor for SourceExtractor {
+    fn matches(&self, ext: &str) -> bool {
+        matches!(
+            ext,
+            "rs" | "py" | "js" | "ts" | "go" | "java" | "c" | "h" | "cpp" | "hpp" | "rb" | "sh"
+                | "toml" | "json" | "yaml" | "yml"
+        )
+    }
+
+    fn extract(&self, path: &Path) -> Option<String> {
+        read_text_capped(path)
+    }
+}
+
+/// Built once and reused for every scanned file, instead of allocating a
+/// fresh set of boxed extractors per call.
+fn extractor_registry() -> &'static [Box<dyn Extractor + Send + Sync>] {
+    static REGISTRY: OnceLock<Vec<Box<dyn Extractor + Send + Sync>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        vec![
+            Box::new(PlainTextExtractor),
+            Box::new(MarkdownExtractor),
+            Box::new(SourceExtractor),
+        ]
+    })
+}
+
+/// Finds the first registered extractor whose extension matches `path` and
+/// runs it, returning `None` for extensionless or unrecognized files.
+fn extract_text(path: &Path) -> Option<String> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    extractor_registry()
+        .iter()
+        .find(|extractor| extractor.matches(&ext))
+        .and_then(|extractor| extractor.extract(path))
+}
+
+/// Scans `file` for metadata and, only when `index_content` is set, extracted
+/// body text for the full-text index. Like `hash_contents`, this is opt-in:
+/// reading and extracting file contents is as I/O heavy as hashing them, and
+/// a metadata cataloger shouldn't pay that cost on every run unless asked.
+fn scan_entry(file: &Path, hash_contents: bool, index_content: bool) -> Option<(FileInfo, Option<String>)> {
+    let info = scan_file(file, hash_contents)?;
+    let text = if index_content { extract_text(file) } else { None };
+    Some((info, text))
+}
+
+/// Registers `content_hash` in the `contents` table, bumping `ref_count` when
+/// the hash is already known from another file. Must run before the `files`
+/// row that references it is written, so `content_hash` always names a
+/// `contents` row that already exists.
+fn increment_content_hash(
+    stmt: &mut rusqlite::Statement,
+    content_hash: &Option<String>,
+    file_size: u64,
+) -> SqliteResult<()> {
+    if let Some(hash) = content_hash {
+        stmt.execute(params![hash, file_size])?;
+    }
+    Ok(())
+}
+
+/// Releases one reference to `hash` in the `contents` table, clamped at zero
+/// so a file whose hash changed (or that was removed) doesn't leave another
+/// file's still-live reference undercounted.
+fn decrement_content_hash(stmt: &mut rusqlite::Statement, hash: &str) -> SqliteResult<()> {
+    stmt.execute(params![hash])?;
+    Ok(())
+}
+
+fn save_batch_to_db(conn: &mut Connection, batch: &[(FileInfo, Option<String>)]) -> SqliteResult<()> {
     let tx = conn.transaction()?;
     {
         let mut stmt = tx.prepare(
-            "INSERT INTO files (file_name, file_path, last_access_date, creation_date, update_date, full_path) 
-             VALUES (?, ?, ?, ?, ?, ?)"
+            "INSERT INTO files (file_name, file_path, last_access_date, creation_date, update_date, full_path, file_size, content_hash)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(full_path) DO UPDATE SET
+                file_name = excluded.file_name,
+                file_path = excluded.file_path,
+                last_access_date = excluded.last_access_date,
+                creation_date = excluded.creation_date,
+                update_date = excluded.update_date,
+                file_size = excluded.file_size,
+                content_hash = excluded.content_hash",
         )?;
-        for file_info in batch {
+        let mut contents_stmt = tx.prepare(
+            "INSERT INTO contents (hash, size, ref_count) VALUES (?, ?, 1)
+             ON CONFLICT(hash) DO UPDATE SET ref_count = ref_count + 1",
+        )?;
+        let mut fts_stmt =
+            tx.prepare("INSERT INTO files_fts (full_path, body) VALUES (?, ?)")?;
+        for (file_info, body) in batch {
+            increment_content_hash(&mut contents_stmt, &file_info.content_hash, file_info.file_size)?;
             stmt.execute(params![
                 file_info.file_name,
                 file_info.file_path,
                 file_info.last_access_date,
                 file_info.creation_date,
                 file_info.update_date,
-                file_info.full_path
+                file_info.full_path,
+                file_info.file_size,
+                file_info.content_hash
             ])?;
+            if let Some(body) = body {
+                fts_stmt.execute(params![file_info.full_path, body])?;
+            }
         }
     }
     tx.commit()
 }
 
+/// Inserts or updates a batch of `FileInfo` rows keyed by `full_path`, returning
+/// the number of rows that were newly added and the number that were modified
+/// since the last sync (determined by a change in `update_date` or `file_size`).
+///
+/// `contents.ref_count` is only adjusted when a row's `content_hash` actually
+/// changes (new file, re-hashed to a different digest, or removed) — an
+/// unchanged file re-synced on every run must not keep bumping the count of
+/// a hash it already held a reference to.
+///
+/// `hash_contents` mirrors the flag the batch was scanned with: when it's
+/// `false`, every `content_hash` in `batch` is `None` regardless of what's
+/// stored, so the existing hash and its `contents` reference are left alone
+/// rather than read as "the file's hash was cleared".
+fn sync_batch_to_db(
+    conn: &mut Connection,
+    batch: &[(FileInfo, Option<String>)],
+    hash_contents: bool,
+) -> SqliteResult<(u64, u64)> {
+    let tx = conn.transaction()?;
+    let mut added = 0u64;
+    let mut updated = 0u64;
+    {
+        let mut select_stmt = tx.prepare(
+            "SELECT update_date, file_size, content_hash FROM files WHERE full_path = ?",
+        )?;
+        let mut upsert_stmt = tx.prepare(
+            "INSERT INTO files (file_name, file_path, last_access_date, creation_date, update_date, full_path, file_size, content_hash)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(full_path) DO UPDATE SET
+                file_name = excluded.file_name,
+                file_path = excluded.file_path,
+                last_access_date = excluded.last_access_date,
+                creation_date = excluded.creation_date,
+                update_date = excluded.update_date,
+                file_size = excluded.file_size,
+                content_hash = excluded.content_hash",
+        )?;
+        let mut increment_stmt = tx.prepare(
+            "INSERT INTO contents (hash, size, ref_count) VALUES (?, ?, 1)
+             ON CONFLICT(hash) DO UPDATE SET ref_count = ref_count + 1",
+        )?;
+        let mut decrement_stmt =
+            tx.prepare("UPDATE contents SET ref_count = MAX(ref_count - 1, 0) WHERE hash = ?")?;
+        let mut delete_fts_stmt = tx.prepare("DELETE FROM files_fts WHERE full_path = ?")?;
+        let mut fts_stmt =
+            tx.prepare("INSERT INTO files_fts (full_path, body) VALUES (?, ?)")?;
+        for (file_info, body) in batch {
+            let existing: Option<(Option<String>, u64, Option<String>)> = select_stmt
+                .query_row(params![file_info.full_path], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })
+                .optional()?;
+
+            let old_hash = existing.as_ref().and_then(|(_, _, hash)| hash.clone());
+            // Hashing wasn't requested this run, so `file_info.content_hash`
+            // is always `None` and carries no information — keep whatever
+            // hash (and reference) is already on file instead of reading a
+            // missing new hash as "the file's content_hash was cleared".
+            let new_hash = if hash_contents {
+                file_info.content_hash.clone()
+            } else {
+                old_hash.clone()
+            };
+            let hash_changed = hash_contents && old_hash != new_hash;
 
+            // The new hash's `contents` row must exist before `files` can
+            // reference it, so increment before the upsert and decrement
+            // the stale reference only after.
+            if hash_changed {
+                increment_content_hash(&mut increment_stmt, &new_hash, file_info.file_size)?;
+            }
 
-fn setup_db(conn: &Connection) -> SqliteResult<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS files (
-            id INTEGER PRIMARY KEY,
-            file_name TEXT NOT NULL,
-            file_path TEXT NOT NULL,
-            last_access_date TEXT,
-            creation_date TEXT,
-            update_date TEXT,
-            full_path TEXT NOT NULL
-        )",
-        [],
-    )?;
+            upsert_stmt.execute(params![
+                file_info.file_name,
+                file_info.file_path,
+                file_info.last_access_date,
+                file_info.creation_date,
+                file_info.update_date,
+                file_info.full_path,
+                file_info.file_size,
+                new_hash
+            ])?;
+
+            if hash_changed {
+                if let Some(old_hash) = &old_hash {
+                    decrement_content_hash(&mut decrement_stmt, old_hash)?;
+                }
+            }
+
+            delete_fts_stmt.execute(params![file_info.full_path])?;
+            if let Some(body) = body {
+                fts_stmt.execute(params![file_info.full_path, body])?;
+            }
+
+            match existing {
+                None => added += 1,
+                Some((update_date, file_size, _)) => {
+                    if update_date != file_info.update_date || file_size != file_info.file_size {
+                        updated += 1;
+                    }
+                }
+            }
+        }
+    }
+    tx.commit()?;
+    Ok((added, updated))
+}
+
+/// Deletes rows whose `full_path` was not encountered during the current sync
+/// run, returning the number of rows removed. Releases each removed row's
+/// `contents` reference so `ref_count` stays accurate after the delete.
+fn remove_unseen_rows(conn: &mut Connection, seen: &HashSet<String>) -> SqliteResult<u64> {
+    let tx = conn.transaction()?;
+    let stale: Vec<(String, Option<String>)> = {
+        let mut stmt = tx.prepare("SELECT full_path, content_hash FROM files")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+        let mut stale = Vec::new();
+        for row in rows {
+            let (full_path, content_hash) = row?;
+            if !seen.contains(&full_path) {
+                stale.push((full_path, content_hash));
+            }
+        }
+        stale
+    };
+    {
+        let mut delete_stmt = tx.prepare("DELETE FROM files WHERE full_path = ?")?;
+        let mut delete_fts_stmt = tx.prepare("DELETE FROM files_fts WHERE full_path = ?")?;
+        let mut decrement_stmt =
+            tx.prepare("UPDATE contents SET ref_count = MAX(ref_count - 1, 0) WHERE hash = ?")?;
+        for (full_path, content_hash) in &stale {
+            delete_stmt.execute(params![full_path])?;
+            delete_fts_stmt.execute(params![full_path])?;
+            if let Some(hash) = content_hash {
+                decrement_content_hash(&mut decrement_stmt, hash)?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(stale.len() as u64)
+}
+
+/// Brings `conn` up to the latest schema by applying any migrations embedded
+/// from `migrations/` that it hasn't already seen, tracked in refinery's own
+/// `refinery_schema_history` table. Safe to call on every open: existing
+/// databases are upgraded in place and already-applied migrations are no-ops.
+fn run_migrations(conn: &mut Connection) -> Result<(), refinery::Error> {
+    migrations::runner().run(conn)?;
+    Ok(())
+}
+
+fn wipe_tables(conn: &Connection) -> SqliteResult<()> {
     conn.execute("DELETE FROM files", [])?;
+    conn.execute("DELETE FROM contents", [])?;
+    conn.execute("DELETE FROM files_fts", [])?;
     Ok(())
 }
 
+/// Tunes a connection that will be the sole writer for the life of a scan:
+/// WAL lets readers proceed without blocking on the writer, relaxing
+/// `synchronous` to `NORMAL` avoids an fsync per transaction while still
+/// being crash-safe under WAL, and enabling `foreign_keys` makes the
+/// `files.content_hash -> contents.hash` reference real instead of a comment.
+fn configure_writer_connection(conn: &Connection) -> SqliteResult<()> {
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    Ok(())
+}
+
+type StoreResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Abstracts the persistence layer so the scan pipeline isn't tied to SQLite:
+/// `SqliteStore` is the default (SQL querying, FTS), while `SledStore` trades
+/// those for a log-structured, single-writer-friendly embedded KV store that
+/// avoids SQLite's single-writer bottleneck on write-heavy full-drive scans.
+trait IndexStore {
+    fn setup(&mut self) -> StoreResult<()>;
+    fn save_batch(&mut self, batch: &[(FileInfo, Option<String>)]) -> StoreResult<()>;
+    fn finish(&mut self) -> StoreResult<()>;
+
+    /// Whether this store does anything with the extracted body text passed
+    /// to `save_batch`. Stores that answer `false` (e.g. `SledStore`) only
+    /// ever discard it, so the caller can skip extraction up front instead of
+    /// paying for it on every file and throwing the result away.
+    fn supports_content(&self) -> bool {
+        true
+    }
+}
+
+struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    fn open(db_path: &str) -> StoreResult<Self> {
+        Ok(Self {
+            conn: Connection::open(db_path)?,
+        })
+    }
+}
+
+impl IndexStore for SqliteStore {
+    fn setup(&mut self) -> StoreResult<()> {
+        run_migrations(&mut self.conn)?;
+        wipe_tables(&self.conn)?;
+        configure_writer_connection(&self.conn)?;
+        Ok(())
+    }
+
+    fn save_batch(&mut self, batch: &[(FileInfo, Option<String>)]) -> StoreResult<()> {
+        save_batch_to_db(&mut self.conn, batch)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> StoreResult<()> {
+        Ok(())
+    }
+}
+
+/// Keys entries by `full_path` and serializes `FileInfo` as the value, so a
+/// scan is just a sequence of puts with no table/transaction machinery.
+struct SledStore {
+    db: sled::Db,
+}
+
+impl SledStore {
+    fn open(db_path: &str) -> StoreResult<Self> {
+        Ok(Self {
+            db: sled::open(db_path)?,
+        })
+    }
+}
+
+impl IndexStore for SledStore {
+    fn setup(&mut self) -> StoreResult<()> {
+        self.db.clear()?;
+        Ok(())
+    }
+
+    fn save_batch(&mut self, batch: &[(FileInfo, Option<String>)]) -> StoreResult<()> {
+        for (file_info, _body) in batch {
+            let value = serde_json::to_vec(file_info)?;
+            self.db.insert(file_info.full_path.as_bytes(), value)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> StoreResult<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn supports_content(&self) -> bool {
+        false
+    }
+}
+
+/// Picks an `IndexStore` by name: `"sqlite"` (the default) for SQL querying
+/// and FTS, `"sled"` for a pure-Rust embedded KV store when write throughput
+/// matters more than queryability.
+fn make_store(backend: &str, db_path: &str) -> StoreResult<Box<dyn IndexStore + Send>> {
+    match backend {
+        "sled" => Ok(Box::new(SledStore::open(db_path)?)),
+        "sqlite" => Ok(Box::new(SqliteStore::open(db_path)?)),
+        other => Err(format!("unknown backend {other:?}, expected \"sqlite\" or \"sled\"").into()),
+    }
+}
+
+/// Walks `root` and persists everything found via the chosen `backend`
+/// (`"sqlite"` or `"sled"`), replacing whatever was indexed before. The rayon
+/// walk streams entries over a bounded channel to a single dedicated writer
+/// thread instead of collecting the whole tree into memory or opening a
+/// connection per batch: a single long-lived store commits each `batch_size`
+/// buffer while the walk keeps producing, capped by the channel's
+/// back-pressure.
+///
+/// Both `hash_contents` and `index_content` are opt-in: reading a file's full
+/// contents, whether to hash or to extract searchable text, is as I/O heavy
+/// as the metadata walk itself and shouldn't run unless asked for.
 #[pyfunction]
-fn scan_and_save(root: String, db_path: String, batch_size: usize) -> PyResult<()> {
-    let mut conn = Connection::open(&db_path)
+#[pyo3(signature = (root, db_path, batch_size, hash_contents=false, index_content=false, backend=String::from("sqlite")))]
+fn scan_and_save(
+    root: String,
+    db_path: String,
+    batch_size: usize,
+    hash_contents: bool,
+    index_content: bool,
+    backend: String,
+) -> PyResult<()> {
+    let mut store = make_store(&backend, &db_path)
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
-    setup_db(&mut conn)
+    store
+        .setup()
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
 
-    let (tx, rx) = channel();
-    let walker = WalkDir::new(&root).into_iter().filter_map(|e| e.ok());
+    // Extraction is wasted work for a store that can't use the body text
+    // (e.g. `SledStore` discards it in `save_batch`), so fold that into the
+    // flag up front instead of extracting it and throwing it away per file.
+    let index_content = index_content && store.supports_content();
+
+    let (tx, rx) = sync_channel::<(FileInfo, Option<String>)>(batch_size * 4);
+
+    let writer = thread::spawn(move || -> StoreResult<()> {
+        let mut buffer = Vec::with_capacity(batch_size);
+        for entry in rx {
+            buffer.push(entry);
+            if buffer.len() >= batch_size {
+                store.save_batch(&buffer)?;
+                buffer.clear();
+            }
+        }
+        if !buffer.is_empty() {
+            store.save_batch(&buffer)?;
+        }
+        store.finish()
+    });
 
+    let walker = WalkDir::new(&root).into_iter().filter_map(|e| e.ok());
     walker
         .par_bridge()
-        .filter_map(|entry| scan_file(entry.path()))
-        .collect::<Vec<FileInfo>>()
-        .chunks(batch_size)
-        .for_each(|batch| {
-            let tx = tx.clone();
-            let mut conn = Connection::open(&db_path).unwrap();
-            tx.send(save_batch_to_db(&mut conn, batch)).unwrap();
+        .filter_map(|entry| scan_entry(entry.path(), hash_contents, index_content))
+        .for_each(|entry| {
+            let _ = tx.send(entry);
         });
 
     drop(tx);
-    for result in rx {
-        result.map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
-    }
+    writer
+        .join()
+        .expect("writer thread panicked")
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
 
     Ok(())
 }
 
+/// Incrementally re-scans `root` and reconciles `db_path` against it instead of
+/// wiping the table: new paths are inserted, paths whose `update_date` or
+/// `file_size` changed are updated, and paths no longer present on disk are
+/// removed. Returns `(added, updated, removed)` so callers can report a
+/// changelog for the run.
+///
+/// Mirrors `scan_and_save`'s single-writer pipeline: the rayon walk streams
+/// entries over a bounded channel instead of collecting the whole tree into
+/// memory, and one dedicated writer thread owns the only `Connection`
+/// (WAL + `synchronous=NORMAL`), tracking which paths it saw so it can sweep
+/// the rest at the end. Errors propagate as `PyIOError` rather than
+/// panicking the interpreter.
+#[pyfunction]
+#[pyo3(signature = (root, db_path, batch_size, hash_contents=false, index_content=false))]
+fn scan_and_sync(
+    root: String,
+    db_path: String,
+    batch_size: usize,
+    hash_contents: bool,
+    index_content: bool,
+) -> PyResult<(u64, u64, u64)> {
+    let mut setup_conn = Connection::open(&db_path)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+    run_migrations(&mut setup_conn)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+    drop(setup_conn);
+
+    let (tx, rx) = sync_channel::<(FileInfo, Option<String>)>(batch_size * 4);
+
+    let writer_db_path = db_path.clone();
+    let writer = thread::spawn(move || -> SqliteResult<(u64, u64, u64)> {
+        let mut conn = Connection::open(&writer_db_path)?;
+        configure_writer_connection(&conn)?;
+
+        let mut buffer = Vec::with_capacity(batch_size);
+        let mut seen = HashSet::new();
+        let mut added = 0u64;
+        let mut updated = 0u64;
+        for entry in rx {
+            seen.insert(entry.0.full_path.clone());
+            buffer.push(entry);
+            if buffer.len() >= batch_size {
+                let (batch_added, batch_updated) = sync_batch_to_db(&mut conn, &buffer, hash_contents)?;
+                added += batch_added;
+                updated += batch_updated;
+                buffer.clear();
+            }
+        }
+        if !buffer.is_empty() {
+            let (batch_added, batch_updated) = sync_batch_to_db(&mut conn, &buffer, hash_contents)?;
+            added += batch_added;
+            updated += batch_updated;
+        }
+
+        let removed = remove_unseen_rows(&mut conn, &seen)?;
+        Ok((added, updated, removed))
+    });
+
+    let walker = WalkDir::new(&root).into_iter().filter_map(|e| e.ok());
+    walker
+        .par_bridge()
+        .filter_map(|entry| scan_entry(entry.path(), hash_contents, index_content))
+        .for_each(|entry| {
+            let _ = tx.send(entry);
+        });
+
+    drop(tx);
+    writer
+        .join()
+        .expect("writer thread panicked")
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))
+}
+
+/// Runs any pending migrations against `db_path` and returns the resulting
+/// schema version, as tracked in refinery's `refinery_schema_history` table.
+#[pyfunction]
+fn db_version(db_path: String) -> PyResult<i32> {
+    let mut conn = Connection::open(&db_path)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+    run_migrations(&mut conn)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM refinery_schema_history",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))
+}
+
+/// Full-text searches the `files_fts` index built by `scan_and_save` /
+/// `scan_and_sync`, returning matching rows ranked by `bm25` relevance.
+#[pyfunction]
+fn search(db_path: String, query: String) -> PyResult<Vec<FileInfo>> {
+    let mut conn = Connection::open(&db_path)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+    run_migrations(&mut conn)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT f.file_name, f.file_path, f.last_access_date, f.creation_date, f.update_date, f.full_path, f.file_size, f.content_hash
+             FROM files_fts
+             JOIN files f ON f.full_path = files_fts.full_path
+             WHERE files_fts MATCH ?1
+             ORDER BY bm25(files_fts)
+             LIMIT 100",
+        )
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
+    let rows = stmt
+        .query_map(params![query], |row| {
+            Ok(FileInfo {
+                file_name: row.get(0)?,
+                file_path: row.get(1)?,
+                last_access_date: row.get(2)?,
+                creation_date: row.get(3)?,
+                update_date: row.get(4)?,
+                full_path: row.get(5)?,
+                file_size: row.get(6)?,
+                content_hash: row.get(7)?,
+            })
+        })
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row.map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?);
+    }
+    Ok(results)
+}
+
 #[pymodule]
 fn file_scanner(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(scan_and_save, m)?)?;
+    m.add_function(wrap_pyfunction!(scan_and_sync, m)?)?;
+    m.add_function(wrap_pyfunction!(db_version, m)?)?;
+    m.add_function(wrap_pyfunction!(search, m)?)?;
     m.add_class::<FileInfo>()?;
     Ok(())
 }